@@ -1,6 +1,8 @@
 #![no_std]
 
-use soroban_sdk::{contract, contractimpl, contracttype, Address, BytesN, Env, Map, Symbol, Vec};
+use soroban_sdk::{
+    contract, contractimpl, contracttype, token, Address, Bytes, BytesN, Env, Map, Symbol, Vec,
+};
 
 #[contract]
 pub struct Contract;
@@ -10,6 +12,35 @@ const KEY_ENVELOPES: Symbol = Symbol::short("ENVS");
 const KEY_GUARDIANS: Symbol = Symbol::short("GUARD");
 const KEY_RECOVERY_THRESHOLD: Symbol = Symbol::short("R_TH");
 const KEY_RECOVERY_DELAY: Symbol = Symbol::short("R_DL");
+const KEY_RECOVERY_PROPOSAL: Symbol = Symbol::short("R_PROP");
+const KEY_TOKEN: Symbol = Symbol::short("TOKEN");
+const KEY_ENVELOPE_IDS: Symbol = Symbol::short("ENV_IDS");
+
+#[contracttype]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HashAlg {
+    Sha256,
+    Keccak256,
+}
+
+#[contracttype]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EnvelopeStatus {
+    Active,
+    Locked,
+    Vesting,
+    FullyClaimed,
+    Revoked,
+    Expired,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct RecoveryProposal {
+    pub new_owner: Address,
+    pub approvals: Vec<Address>,
+    pub created_ts: u64,
+}
 
 #[contracttype]
 #[derive(Clone)]
@@ -24,8 +55,10 @@ pub struct Envelope {
     pub beneficiary: Address,
     pub amount: i128,
     pub secret_hash: BytesN<32>,
+    pub hash_alg: HashAlg,
     pub unlock_ts: Option<i64>,
     pub vesting: Vec<VestSlice>,
+    pub linear: bool,
     pub claimed: i128,
     pub expiry_ts: Option<i64>,
     pub revoked: bool,
@@ -50,6 +83,111 @@ impl Contract {
             .expect("owner not set")
     }
 
+    fn guardians(env: &Env) -> Vec<Address> {
+        env.storage()
+            .instance()
+            .get(&KEY_GUARDIANS)
+            .unwrap_or(Vec::new(env))
+    }
+
+    fn recovery_threshold(env: &Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&KEY_RECOVERY_THRESHOLD)
+            .expect("recovery threshold not set")
+    }
+
+    fn recovery_delay(env: &Env) -> u64 {
+        env.storage()
+            .instance()
+            .get(&KEY_RECOVERY_DELAY)
+            .expect("recovery delay not set")
+    }
+
+    fn recovery_proposal(env: &Env) -> Option<RecoveryProposal> {
+        env.storage().instance().get(&KEY_RECOVERY_PROPOSAL)
+    }
+
+    fn token_client(env: &Env) -> token::Client<'_> {
+        let token_id: Address = env.storage().instance().get(&KEY_TOKEN).expect("token not set");
+        token::Client::new(env, &token_id)
+    }
+
+    fn envelope_ids(env: &Env) -> Vec<BytesN<32>> {
+        env.storage()
+            .instance()
+            .get(&KEY_ENVELOPE_IDS)
+            .unwrap_or(Vec::new(env))
+    }
+
+    fn save_envelope_ids(env: &Env, ids: &Vec<BytesN<32>>) {
+        env.storage().instance().set(&KEY_ENVELOPE_IDS, ids);
+    }
+
+    fn status_of(env: &Env, env_rec: &Envelope) -> EnvelopeStatus {
+        if env_rec.revoked {
+            return EnvelopeStatus::Revoked;
+        }
+        if env_rec.claimed >= env_rec.amount {
+            return EnvelopeStatus::FullyClaimed;
+        }
+        let now_ts = Contract::now(env) as i64;
+        if let Some(expiry_ts) = env_rec.expiry_ts {
+            if now_ts >= expiry_ts {
+                return EnvelopeStatus::Expired;
+            }
+        }
+        if let Some(unlock_ts) = env_rec.unlock_ts {
+            if now_ts < unlock_ts {
+                return EnvelopeStatus::Locked;
+            }
+        }
+        if !env_rec.vesting.is_empty() {
+            return EnvelopeStatus::Vesting;
+        }
+        EnvelopeStatus::Active
+    }
+
+    fn step_vested_bp(vesting: &Vec<VestSlice>, now_ts: i64) -> u32 {
+        let mut sum_bp: u32 = 0;
+        for vs in vesting.iter() {
+            if vs.ts <= now_ts {
+                sum_bp = sum_bp.saturating_add(vs.bp);
+            }
+        }
+        sum_bp.min(10_000)
+    }
+
+    fn linear_vested_bp(vesting: &Vec<VestSlice>, now_ts: i64) -> u32 {
+        let mut cumulative: u32 = 0;
+        let mut last_passed: Option<(i64, u32)> = None;
+        let mut next_future: Option<(i64, u32)> = None;
+
+        for vs in vesting.iter() {
+            cumulative = cumulative.saturating_add(vs.bp).min(10_000);
+            if vs.ts <= now_ts {
+                last_passed = Some((vs.ts, cumulative));
+            } else if next_future.is_none() {
+                next_future = Some((vs.ts, cumulative));
+            }
+        }
+
+        match (last_passed, next_future) {
+            (None, _) => 0,
+            (Some((_, cum0)), None) => cum0,
+            (Some((t0, cum0)), Some((t1, cum1))) => {
+                let span = t1 - t0;
+                if span <= 0 {
+                    return cum1;
+                }
+                let elapsed = now_ts - t0;
+                let interpolated =
+                    cum0 as i128 + (cum1 as i128 - cum0 as i128) * elapsed as i128 / span as i128;
+                interpolated.clamp(0, 10_000) as u32
+            }
+        }
+    }
+
     fn now(env: &Env) -> u64 {
         env.ledger().timestamp()
     }
@@ -60,6 +198,7 @@ impl Contract {
     pub fn initialize(
         env: Env,
         owner: Address,
+        token: Address,
         guardians: Vec<Address>,
         recovery_threshold: u32,
         recovery_delay: u64,
@@ -68,6 +207,7 @@ impl Contract {
             panic!("already initialized");
         }
         env.storage().instance().set(&KEY_OWNER, &owner);
+        env.storage().instance().set(&KEY_TOKEN, &token);
         env.storage().instance().set(&KEY_GUARDIANS, &guardians);
         env.storage().instance().set(&KEY_RECOVERY_THRESHOLD, &recovery_threshold);
         env.storage().instance().set(&KEY_RECOVERY_DELAY, &recovery_delay);
@@ -80,13 +220,15 @@ impl Contract {
         beneficiary: Address,
         amount: i128,
         secret_hash: BytesN<32>,
+        hash_alg: HashAlg,
         unlock_ts: Option<u64>,
         vesting: Vec<VestSlice>,
+        linear: bool,
         expiry_ts: Option<u64>,
     ) {
         let owner = Contract::owner_address(&env);
         owner.require_auth();
-        
+
         if amount <= 0 {
             panic!("amount must be > 0");
         }
@@ -100,17 +242,30 @@ impl Contract {
             beneficiary,
             amount,
             secret_hash,
+            hash_alg,
             unlock_ts: unlock_ts.map(|ts| ts as i64),
             vesting,
+            linear,
             claimed: 0,
             expiry_ts: expiry_ts.map(|ts| ts as i64),
             revoked: false,
         };
+        Contract::token_client(&env).transfer(&owner, &env.current_contract_address(), &amount);
+
+        let mut ids = Contract::envelope_ids(&env);
+        ids.push_back(envelope_id.clone());
+        Contract::save_envelope_ids(&env, &ids);
+
+        env.events().publish(
+            (Symbol::short("env"), Symbol::short("created")),
+            (envelope_id.clone(), env_rec.beneficiary.clone(), amount),
+        );
+
         m.set(envelope_id, env_rec);
         Contract::save_envelopes(&env, &m);
     }
 
-    pub fn claim(env: Env, envelope_id: BytesN<32>, provided_secret_hash: BytesN<32>) -> i128 {
+    pub fn claim(env: Env, envelope_id: BytesN<32>, preimage: Bytes) -> i128 {
         let mut m = Contract::envelopes_map(&env);
         let env_rec = m.get(envelope_id.clone()).expect("envelope not found");
 
@@ -120,8 +275,12 @@ impl Contract {
 
         env_rec.beneficiary.require_auth();
 
-        if provided_secret_hash != env_rec.secret_hash {
-            panic!("invalid secret");
+        let digest: BytesN<32> = match env_rec.hash_alg {
+            HashAlg::Sha256 => env.crypto().sha256(&preimage).into(),
+            HashAlg::Keccak256 => env.crypto().keccak256(&preimage).into(),
+        };
+        if digest != env_rec.secret_hash {
+            panic!("invalid preimage");
         }
 
         if let Some(unlock_ts) = env_rec.unlock_ts {
@@ -131,20 +290,13 @@ impl Contract {
         }
 
         let now_ts = Contract::now(&env) as i64;
-        let mut sum_bp: u32 = 0;
-        
-        if env_rec.vesting.is_empty() {
-            sum_bp = 10_000;
+        let sum_bp: u32 = if env_rec.vesting.is_empty() {
+            10_000
+        } else if env_rec.linear {
+            Contract::linear_vested_bp(&env_rec.vesting, now_ts)
         } else {
-            for vs in env_rec.vesting.iter() {
-                if vs.ts <= now_ts {
-                    sum_bp = sum_bp.saturating_add(vs.bp);
-                }
-            }
-            if sum_bp > 10_000 {
-                sum_bp = 10_000;
-            }
-        }
+            Contract::step_vested_bp(&env_rec.vesting, now_ts)
+        };
 
         let vested_amount = (env_rec.amount * sum_bp as i128) / 10_000i128;
         if vested_amount <= env_rec.claimed {
@@ -157,6 +309,17 @@ impl Contract {
         m.set(envelope_id, updated_env);
         Contract::save_envelopes(&env, &m);
 
+        Contract::token_client(&env).transfer(
+            &env.current_contract_address(),
+            &env_rec.beneficiary,
+            &delta,
+        );
+
+        env.events().publish(
+            (Symbol::short("env"), Symbol::short("claimed")),
+            (envelope_id, env_rec.beneficiary, delta, preimage),
+        );
+
         delta
     }
 
@@ -173,11 +336,22 @@ impl Contract {
         if env_rec.claimed >= env_rec.amount {
             panic!("already fully claimed");
         }
-        
+
+        let unclaimed = env_rec.amount - env_rec.claimed;
+
         let mut updated_env = env_rec.clone();
         updated_env.revoked = true;
         m.set(envelope_id, updated_env);
         Contract::save_envelopes(&env, &m);
+
+        if unclaimed > 0 {
+            Contract::token_client(&env).transfer(&env.current_contract_address(), &owner, &unclaimed);
+        }
+
+        env.events().publish(
+            (Symbol::short("env"), Symbol::short("revoked")),
+            (envelope_id, unclaimed),
+        );
     }
 
     pub fn refund_owner(env: Env, envelope_id: BytesN<32>) -> i128 {
@@ -210,6 +384,13 @@ impl Contract {
         m.set(envelope_id, updated_env);
         Contract::save_envelopes(&env, &m);
 
+        Contract::token_client(&env).transfer(&env.current_contract_address(), &owner, &unclaimed);
+
+        env.events().publish(
+            (Symbol::short("env"), Symbol::short("refunded")),
+            (envelope_id, owner, unclaimed),
+        );
+
         unclaimed
     }
 
@@ -217,4 +398,103 @@ impl Contract {
         let m = Contract::envelopes_map(&env);
         m.get(envelope_id).expect("envelope not found")
     }
+
+    pub fn status(env: Env, envelope_id: BytesN<32>) -> EnvelopeStatus {
+        let m = Contract::envelopes_map(&env);
+        let env_rec = m.get(envelope_id).expect("envelope not found");
+        Contract::status_of(&env, &env_rec)
+    }
+
+    pub fn list_envelopes(env: Env, start: u32, limit: u32) -> Vec<(BytesN<32>, EnvelopeStatus)> {
+        let ids = Contract::envelope_ids(&env);
+        let m = Contract::envelopes_map(&env);
+        let mut out = Vec::new(&env);
+
+        let end = (start as u64 + limit as u64).min(ids.len() as u64) as u32;
+        let mut i = start;
+        while i < end {
+            let id = ids.get(i).expect("index out of bounds");
+            let env_rec = m.get(id.clone()).expect("envelope not found");
+            let status = Contract::status_of(&env, &env_rec);
+            out.push_back((id, status));
+            i += 1;
+        }
+        out
+    }
+
+    pub fn propose_recovery(env: Env, guardian: Address, new_owner: Address) {
+        guardian.require_auth();
+
+        if !Contract::guardians(&env).contains(&guardian) {
+            panic!("not a guardian");
+        }
+        if Contract::recovery_proposal(&env).is_some() {
+            panic!("recovery already pending");
+        }
+
+        let proposal = RecoveryProposal {
+            new_owner: new_owner.clone(),
+            approvals: Vec::from_array(&env, [guardian.clone()]),
+            created_ts: Contract::now(&env),
+        };
+        env.storage().instance().set(&KEY_RECOVERY_PROPOSAL, &proposal);
+
+        env.events().publish(
+            (Symbol::short("recovery"), Symbol::short("proposed")),
+            (guardian, new_owner),
+        );
+    }
+
+    pub fn approve_recovery(env: Env, guardian: Address) {
+        guardian.require_auth();
+
+        if !Contract::guardians(&env).contains(&guardian) {
+            panic!("not a guardian");
+        }
+
+        let mut proposal = Contract::recovery_proposal(&env).expect("no recovery pending");
+        if proposal.approvals.contains(&guardian) {
+            panic!("guardian already approved");
+        }
+
+        proposal.approvals.push_back(guardian.clone());
+        env.storage().instance().set(&KEY_RECOVERY_PROPOSAL, &proposal);
+
+        env.events().publish(
+            (Symbol::short("recovery"), Symbol::short("approved")),
+            guardian,
+        );
+    }
+
+    pub fn execute_recovery(env: Env) {
+        let proposal = Contract::recovery_proposal(&env).expect("no recovery pending");
+
+        if proposal.approvals.len() < Contract::recovery_threshold(&env) {
+            panic!("not enough approvals");
+        }
+        if Contract::now(&env) < proposal.created_ts + Contract::recovery_delay(&env) {
+            panic!("recovery still timelocked");
+        }
+
+        env.storage().instance().set(&KEY_OWNER, &proposal.new_owner);
+        env.storage().instance().remove(&KEY_RECOVERY_PROPOSAL);
+
+        env.events().publish(
+            (Symbol::short("recovery"), Symbol::short("executed")),
+            proposal.new_owner,
+        );
+    }
+
+    pub fn cancel_recovery(env: Env) {
+        let owner = Contract::owner_address(&env);
+        owner.require_auth();
+
+        if Contract::recovery_proposal(&env).is_none() {
+            panic!("no recovery pending");
+        }
+        env.storage().instance().remove(&KEY_RECOVERY_PROPOSAL);
+
+        env.events()
+            .publish((Symbol::short("recovery"), Symbol::short("cancelled")), ());
+    }
 }
\ No newline at end of file